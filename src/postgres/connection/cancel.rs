@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UnixStream};
+
+use crate::error::Error;
+
+// The cancel request is not framed like other startup messages: it is a
+// fixed 16-byte payload with no trailing data, so it is cheaper to write
+// by hand than to route through `protocol::Encode`.
+const CANCEL_REQUEST_CODE: i32 = 80_877_102;
+
+/// Where a connection was actually dialed, so a `CancelToken` cloned out
+/// of it knows how to open its own, independent connection to the same
+/// backend.
+#[derive(Debug, Clone)]
+pub(super) enum ConnectTarget {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// A handle that can be used to ask the server to cancel whatever
+/// statement is currently running on the connection it was cloned from.
+///
+/// Unlike the connection itself, a `CancelToken` is `Clone` and does not
+/// borrow the connection, so it can be handed to another task and used
+/// to interrupt a long-running `fetch`/`execute` while it is in flight.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    target: ConnectTarget,
+    process_id: u32,
+    secret_key: u32,
+}
+
+impl CancelToken {
+    pub(super) fn new(target: ConnectTarget, process_id: u32, secret_key: u32) -> Self {
+        Self {
+            target,
+            process_id,
+            secret_key,
+        }
+    }
+
+    /// Ask the server to cancel whatever is currently running on the
+    /// connection this token was cloned from.
+    ///
+    /// This opens a brand new connection to the server, sends the
+    /// CancelRequest message, and closes the socket without waiting for
+    /// a reply; the server never sends one, and a stray cancel delivered
+    /// after the original statement has already finished is a no-op.
+    pub async fn cancel(&self) -> Result<(), Error> {
+        let mut buf = Vec::with_capacity(16);
+        buf.extend_from_slice(&16i32.to_be_bytes());
+        buf.extend_from_slice(&CANCEL_REQUEST_CODE.to_be_bytes());
+        buf.extend_from_slice(&self.process_id.to_be_bytes());
+        buf.extend_from_slice(&self.secret_key.to_be_bytes());
+
+        match &self.target {
+            ConnectTarget::Tcp(addr) => {
+                let mut stream = TcpStream::connect(addr).await.map_err(Error::Io)?;
+                stream.write_all(&buf).await.map_err(Error::Io)?;
+                stream.shutdown(std::net::Shutdown::Both).map_err(Error::Io)?;
+            }
+
+            ConnectTarget::Unix(path) => {
+                let mut stream = UnixStream::connect(path).await.map_err(Error::Io)?;
+                stream.write_all(&buf).await.map_err(Error::Io)?;
+                stream.shutdown(std::net::Shutdown::Both).map_err(Error::Io)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::future::BoxFuture;
+use futures_core::stream::BoxStream;
+use futures_sink::Sink;
+use futures_util::stream;
+
+use super::PostgresRawConnection;
+use crate::error::Error;
+use crate::postgres::protocol::{self, Message};
+
+impl PostgresRawConnection {
+    /// Start a `COPY ... FROM STDIN` and return a [`Sink`] that raw row
+    /// buffers can be written to; each item is framed as a `CopyData`
+    /// message. Closing the sink (e.g. via `SinkExt::close`) sends
+    /// `CopyDone` and waits for the server's `CommandComplete`.
+    pub async fn copy_in<'c>(&'c mut self, query: &str) -> Result<CopyIn<'c>, Error> {
+        self.write(protocol::Query { query });
+        self.flush().await?;
+
+        loop {
+            match self.receive().await? {
+                Some(Message::CopyInResponse(_)) => break,
+                Some(_) => {}
+                None => return Err(Error::Io(connection_closed())),
+            }
+        }
+
+        Ok(CopyIn {
+            state: CopyState::Ready(self),
+        })
+    }
+
+    /// Start a `COPY ... TO STDOUT` and return a stream of the raw row
+    /// buffers the server sends back, one per `CopyData` message, ending
+    /// when the server sends `CopyDone`.
+    pub async fn copy_out<'c>(&'c mut self, query: &str) -> Result<BoxStream<'c, Result<Vec<u8>, Error>>, Error> {
+        self.write(protocol::Query { query });
+        self.flush().await?;
+
+        loop {
+            match self.receive().await? {
+                Some(Message::CopyOutResponse(_)) => break,
+                Some(_) => {}
+                None => return Err(Error::Io(connection_closed())),
+            }
+        }
+
+        Ok(Box::pin(stream::unfold(self, |conn| async move {
+            loop {
+                match conn.receive().await {
+                    Ok(Some(Message::CopyData(data))) => return Some((Ok(data), conn)),
+
+                    Ok(Some(Message::CopyDone)) => {
+                        return match drain_to_ready(conn).await {
+                            Ok(()) => None,
+                            Err(err) => Some((Err(err), conn)),
+                        };
+                    }
+
+                    Ok(Some(_)) => continue,
+                    Ok(None) => return None,
+
+                    // An `ErrorResponse` mid-copy aborts it; `receive()`
+                    // already drains through the `ReadyForQuery` that
+                    // follows before returning the error, so the connection
+                    // is already usable again here without us draining a
+                    // second time (which would just hang waiting for a
+                    // `ReadyForQuery` that was already consumed).
+                    Err(err) => return Some((Err(err), conn)),
+                }
+            }
+        })))
+    }
+}
+
+// Drain messages up to and including the ReadyForQuery that follows a
+// completed (or aborted) COPY, so the connection is usable again afterward.
+async fn drain_to_ready(conn: &mut PostgresRawConnection) -> Result<(), Error> {
+    while !matches!(conn.receive().await?, Some(Message::ReadyForQuery(_)) | None) {}
+
+    Ok(())
+}
+
+fn connection_closed() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "connection closed during COPY")
+}
+
+// Drives one in-flight `CopyData` write, handing the connection back to
+// `Ready` once it completes so the next `start_send` or `poll_close` can
+// use it again, or the final `CopyDone` round trip, which ends in `Done`.
+enum CopyState<'c> {
+    Ready(&'c mut PostgresRawConnection),
+    Sending(BoxFuture<'c, Result<&'c mut PostgresRawConnection, Error>>),
+    Closing(BoxFuture<'c, Result<(), Error>>),
+    Done,
+}
+
+/// A [`Sink`] for the raw row buffers of a `COPY ... FROM STDIN`, returned
+/// by [`PostgresRawConnection::copy_in`].
+pub struct CopyIn<'c> {
+    state: CopyState<'c>,
+}
+
+impl<'c> CopyIn<'c> {
+    // Poll whatever send/close future is currently in flight to completion.
+    fn poll_conn(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        loop {
+            match &mut self.state {
+                CopyState::Ready(_) | CopyState::Done => return Poll::Ready(Ok(())),
+
+                CopyState::Sending(fut) => match fut.as_mut().poll(cx) {
+                    Poll::Ready(Ok(conn)) => {
+                        self.state = CopyState::Ready(conn);
+                        return Poll::Ready(Ok(()));
+                    }
+                    Poll::Ready(Err(err)) => {
+                        self.state = CopyState::Done;
+                        return Poll::Ready(Err(err));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+
+                CopyState::Closing(fut) => match fut.as_mut().poll(cx) {
+                    Poll::Ready(result) => {
+                        self.state = CopyState::Done;
+                        return Poll::Ready(result);
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
+
+impl<'c> Sink<Vec<u8>> for CopyIn<'c> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_conn(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Error> {
+        let this = self.get_mut();
+
+        let conn = match std::mem::replace(&mut this.state, CopyState::Done) {
+            CopyState::Ready(conn) => conn,
+            _ => panic!("CopyIn::start_send called without a successful poll_ready"),
+        };
+
+        this.state = CopyState::Sending(Box::pin(async move {
+            conn.write(protocol::CopyData { data: &item });
+            conn.flush().await?;
+
+            Ok(conn)
+        }));
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_conn(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+
+        if let Poll::Pending = this.poll_conn(cx) {
+            return Poll::Pending;
+        }
+
+        if let CopyState::Ready(conn) = std::mem::replace(&mut this.state, CopyState::Done) {
+            this.state = CopyState::Closing(Box::pin(async move {
+                conn.write(protocol::CopyDone);
+                conn.flush().await?;
+                // Drains the `CommandComplete`/`ReadyForQuery` of a normal
+                // completion. If the server instead sent an `ErrorResponse`
+                // aborting the copy, `receive()` has already drained past
+                // its `ReadyForQuery` by the time this `?` sees the error,
+                // so the connection is left usable either way.
+                drain_to_ready(conn).await
+            }));
+        }
+
+        this.poll_conn(cx)
+    }
+}
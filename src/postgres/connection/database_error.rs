@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// A structured error returned by the server in response to a failed
+/// query, decoded from an `ErrorResponse` message.
+///
+/// See <https://www.postgresql.org/docs/current/protocol-error-fields.html>
+/// for the meaning of each field.
+#[derive(Debug, Clone, Default)]
+pub struct PostgresDatabaseError {
+    severity: String,
+    severity_non_localized: Option<String>,
+    code: String,
+    message: String,
+    detail: Option<String>,
+    hint: Option<String>,
+    position: Option<u32>,
+    where_: Option<String>,
+    table: Option<String>,
+    schema: Option<String>,
+    column: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    routine: Option<String>,
+}
+
+impl PostgresDatabaseError {
+    /// The five-character SQLSTATE code, e.g. `23505` for a unique violation.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The primary human-readable error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// The 1-indexed character offset into the original query the error refers to, if any.
+    pub fn position(&self) -> Option<u32> {
+        self.position
+    }
+
+    // An ErrorResponse (and NoticeResponse, which shares the same wire
+    // format) body is a sequence of `{field type: u8}{value: NUL-terminated UTF-8}`
+    // pairs, terminated by a zero byte in place of a field type.
+    pub(super) fn parse(mut body: &[u8]) -> Self {
+        let mut error = Self::default();
+
+        while let Some((&field, rest)) = body.split_first() {
+            if field == 0 {
+                break;
+            }
+
+            let end = rest.iter().position(|&byte| byte == 0).unwrap_or(rest.len());
+            let value = String::from_utf8_lossy(&rest[..end]).into_owned();
+            body = rest.get(end + 1..).unwrap_or(&[]);
+
+            match field {
+                b'S' => error.severity = value,
+                b'V' => error.severity_non_localized = Some(value),
+                b'C' => error.code = value,
+                b'M' => error.message = value,
+                b'D' => error.detail = Some(value),
+                b'H' => error.hint = Some(value),
+                b'P' => error.position = value.parse().ok(),
+                b'W' => error.where_ = Some(value),
+                b't' => error.table = Some(value),
+                b'n' => error.schema = Some(value),
+                b'c' => error.column = Some(value),
+                b'F' => error.file = Some(value),
+                b'L' => error.line = value.parse().ok(),
+                b'R' => error.routine = Some(value),
+                // Unrecognized field codes are reserved for future protocol versions.
+                _ => {}
+            }
+        }
+
+        error
+    }
+}
+
+impl fmt::Display for PostgresDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for PostgresDatabaseError {}
@@ -0,0 +1,176 @@
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use url::Url;
+
+use super::cancel::ConnectTarget;
+use super::{AsyncStream, PostgresRawConnection};
+use crate::error::Error;
+use crate::postgres::protocol::{self, Message};
+
+// The SSLRequest message is not framed like the rest of the startup
+// sequence (it has no trailing data), so it's simplest to write by hand.
+const SSL_REQUEST_CODE: i32 = 80_877_103;
+
+// Connect to `url`: over a Unix domain socket when it names one (as libpq
+// does for a `/`-prefixed host or a `host=/path/to/dir` query parameter),
+// with no TCP fallback if that connect fails; otherwise by resolving the
+// hostname and trying each address in turn.
+pub(super) async fn connect(url: &Url) -> Result<(Box<dyn AsyncStream>, ConnectTarget), Error> {
+    if let Some(path) = unix_socket_path(url) {
+        let stream = UnixStream::connect(&path).await.map_err(Error::Io)?;
+
+        return Ok((Box::new(stream), ConnectTarget::Unix(path)));
+    }
+
+    let host = url.host_str().unwrap_or("localhost");
+    let port = url.port().unwrap_or(5432);
+    let ssl_mode = SslMode::from_url(url);
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(Error::Io)?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(Error::Configuration(
+            format!("could not resolve host `{}`", host).into(),
+        ));
+    }
+
+    let mut last_err = None;
+
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => {
+                let stream = negotiate_tls(stream, host, ssl_mode).await?;
+
+                return Ok((stream, ConnectTarget::Tcp(addr)));
+            }
+
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(Error::Io(last_err.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")
+    })))
+}
+
+// `postgres://...?host=/var/run/postgresql` or a `/`-prefixed host both mean
+// "connect to a Unix domain socket in this directory" instead of over TCP.
+fn unix_socket_path(url: &Url) -> Option<PathBuf> {
+    let dir = match url.host_str() {
+        Some(host) if host.starts_with('/') => host.to_owned(),
+
+        _ => url.query_pairs().find_map(|(k, v)| {
+            if k == "host" && v.starts_with('/') {
+                Some(v.into_owned())
+            } else {
+                None
+            }
+        })?,
+    };
+
+    let port = url.port().unwrap_or(5432);
+
+    Some(PathBuf::from(dir).join(format!(".s.PGSQL.{}", port)))
+}
+
+/// How eagerly to negotiate TLS for a new connection, mirroring libpq's `sslmode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SslMode {
+    /// Never attempt TLS.
+    Disable,
+    /// Attempt TLS, falling back to a plaintext connection if the server declines.
+    Prefer,
+    /// Require TLS; fail the connection if the server declines.
+    Require,
+}
+
+impl SslMode {
+    pub(super) fn from_url(url: &Url) -> Self {
+        match url
+            .query_pairs()
+            .find_map(|(k, v)| if k == "sslmode" { Some(v.into_owned()) } else { None })
+            .as_deref()
+        {
+            Some("disable") => SslMode::Disable,
+            Some("require") => SslMode::Require,
+            _ => SslMode::Prefer,
+        }
+    }
+}
+
+// Ask the server whether it is willing to speak TLS on this socket and, if
+// so, wrap it. Must happen before anything else is written to the stream.
+async fn negotiate_tls<S>(mut stream: S, host: &str, mode: SslMode) -> Result<Box<dyn AsyncStream>, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    if mode == SslMode::Disable {
+        return Ok(Box::new(stream));
+    }
+
+    let mut buf = Vec::with_capacity(8);
+    buf.extend_from_slice(&8i32.to_be_bytes());
+    buf.extend_from_slice(&SSL_REQUEST_CODE.to_be_bytes());
+    stream.write_all(&buf).await.map_err(Error::Io)?;
+
+    let mut response = [0u8; 1];
+    stream.read_exact(&mut response).await.map_err(Error::Io)?;
+
+    match response[0] {
+        b'S' => {
+            let connector = async_native_tls::TlsConnector::new();
+            let stream = connector.connect(host, stream).await.map_err(Error::Tls)?;
+
+            Ok(Box::new(stream))
+        }
+
+        b'N' if mode == SslMode::Require => Err(Error::Configuration(
+            "server does not support TLS, but sslmode=require was specified".into(),
+        )),
+
+        _ => Ok(Box::new(stream)),
+    }
+}
+
+// Send the startup message and drive the connection through authentication
+// until the server reports it is ready for queries.
+pub(super) async fn establish(conn: &mut PostgresRawConnection, url: &Url) -> Result<(), Error> {
+    let username = match url.username() {
+        "" => "postgres",
+        username => username,
+    };
+
+    let database = match url.path().trim_start_matches('/') {
+        "" => username,
+        database => database,
+    };
+
+    conn.write(protocol::StartupMessage {
+        params: &[("user", username), ("database", database)],
+    });
+    conn.flush().await?;
+
+    loop {
+        match conn.receive().await? {
+            Some(Message::AuthenticationOk) => {}
+
+            Some(Message::BackendKeyData(body)) => {
+                conn.process_id = body.process_id;
+                conn.secret_key = body.secret_key;
+            }
+
+            Some(Message::ReadyForQuery(_)) => break,
+
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
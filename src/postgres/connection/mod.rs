@@ -5,23 +5,49 @@ use super::{
 use crate::{connection::RawConnection, error::Error, query::QueryParameters};
 use bytes::{BufMut, BytesMut};
 use futures_core::{future::BoxFuture, stream::BoxStream};
-use std::{
-    io,
-    net::{IpAddr, Shutdown, SocketAddr},
-};
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::TcpStream,
-};
+use futures_util::{stream, TryFutureExt as _};
+use std::collections::{HashMap, VecDeque};
+use std::net::Shutdown;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use url::Url;
 
+mod cancel;
+mod copy;
+mod database_error;
 mod establish;
 mod execute;
 mod fetch;
 mod fetch_optional;
+mod notification;
+mod pipeline;
+mod stmt_cache;
+
+use cancel::ConnectTarget;
+pub use cancel::CancelToken;
+pub use copy::CopyIn;
+pub use database_error::PostgresDatabaseError;
+pub use establish::SslMode;
+pub use notification::Notification;
+pub use pipeline::{Pipeline, PipelineResponse};
+
+use stmt_cache::{Statement, StatementCache};
+
+// Default number of server-side prepared statements to keep cached
+// before the least-recently-used one is closed to make room.
+const STATEMENT_CACHE_CAPACITY: usize = 100;
+
+// Implemented for every stream we can speak the Postgres wire protocol
+// over (plain TCP, Unix domain sockets, and either wrapped in TLS), so
+// the rest of the connection code doesn't need to know which it has.
+pub(super) trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S> AsyncStream for S where S: AsyncRead + AsyncWrite + Unpin + Send {}
 
 pub struct PostgresRawConnection {
-    stream: TcpStream,
+    stream: Box<dyn AsyncStream>,
+
+    // Where `stream` is connected to, so a `CancelToken` can open its own connection to it
+    target: ConnectTarget,
 
     // Do we think that there is data in the read buffer to be decoded
     stream_readable: bool,
@@ -41,30 +67,35 @@ pub struct PostgresRawConnection {
 
     // Backend-unique key to use to send a cancel query message to the server
     secret_key: u32,
+
+    // Server-side prepared statements, keyed by the SQL text that produced them
+    statement_cache: StatementCache,
+
+    // Asynchronous NOTIFYs received but not yet drained by `notifications()`
+    notifications: VecDeque<Notification>,
+
+    // Server run-time parameters (e.g. `server_version`, `TimeZone`), updated by ParameterStatus
+    parameters: HashMap<String, String>,
 }
 
 impl PostgresRawConnection {
     async fn establish(url: &str) -> Result<Self, Error> {
-        // TODO: Handle errors
-        let url = Url::parse(url).unwrap();
-
-        let host = url.host_str().unwrap_or("localhost");
-        let port = url.port().unwrap_or(5432);
+        let url = Url::parse(url).map_err(|err| Error::Configuration(err.to_string().into()))?;
 
-        // FIXME: handle errors
-        let host: IpAddr = host.parse().unwrap();
-        let addr: SocketAddr = (host, port).into();
-
-        let stream = TcpStream::connect(&addr).await.map_err(Error::Io)?;
+        let (stream, target) = establish::connect(&url).await?;
 
         let mut conn = Self {
             wbuf: Vec::with_capacity(1024),
             rbuf: BytesMut::with_capacity(1024 * 8),
             stream,
+            target,
             stream_readable: false,
             stream_eof: false,
             process_id: 0,
             secret_key: 0,
+            statement_cache: StatementCache::new(STATEMENT_CACHE_CAPACITY),
+            notifications: VecDeque::new(),
+            parameters: HashMap::new(),
         };
 
         establish::establish(&mut conn, &url).await?;
@@ -82,22 +113,58 @@ impl PostgresRawConnection {
 
     // Wait and return the next message to be received from Postgres.
     async fn receive(&mut self) -> Result<Option<Message>, Error> {
+        // An `ErrorResponse` we've decoded but not yet returned: Postgres
+        // always follows it with a `ReadyForQuery` before the connection is
+        // usable again, so we keep draining (right here, without handing
+        // control back to the caller) until we reach it, then surface the
+        // error. Returning as soon as the `ErrorResponse` is decoded would
+        // leave that `ReadyForQuery` sitting in `rbuf` for the next
+        // unrelated call to misread as its own.
+        let mut pending_error = None;
+
         loop {
             if self.stream_eof {
-                // Reached end-of-file on a previous read call.
-                return Ok(None);
+                // Reached end-of-file on a previous read call. Surface a
+                // still-pending error rather than silently dropping it if
+                // the server closed the connection right after reporting it.
+                return match pending_error {
+                    Some(error) => Err(error),
+                    None => Ok(None),
+                };
             }
 
             if self.stream_readable {
                 loop {
                     match Message::decode(&mut self.rbuf) {
-                        Some(Message::ParameterStatus(_body)) => {
-                            // TODO: not sure what to do with these yet
+                        Some(Message::ParameterStatus(body)) => {
+                            let (name, value) = notification::parse_parameter_status(&body);
+                            self.parameters.insert(name, value);
+                        }
+
+                        Some(message @ Message::NotificationResponse(_)) => {
+                            if let Message::NotificationResponse(body) = &message {
+                                // A malformed body (too short to even hold a
+                                // process ID) is dropped rather than panicking
+                                // on whatever bytes the server sent.
+                                if let Some(notification) = Notification::parse(body) {
+                                    self.notifications.push_back(notification);
+                                }
+                            }
+
+                            // Buffered above, but also handed back to the
+                            // caller so `notifications()` (idle, waiting on
+                            // nothing else) wakes up to pop it instead of
+                            // this loop blocking on the next byte from the
+                            // server, which may never come.
+                            return Ok(Some(message));
+                        }
+
+                        Some(Message::Response(body)) => {
+                            pending_error = Some(Error::Database(database_error::PostgresDatabaseError::parse(&body)));
                         }
 
-                        Some(Message::Response(_body)) => {
-                            // TODO: Transform Errors+ into an error type and return
-                            // TODO: Log all others
+                        Some(Message::ReadyForQuery(_)) if pending_error.is_some() => {
+                            return Err(pending_error.unwrap());
                         }
 
                         Some(message) => {
@@ -149,6 +216,119 @@ impl PostgresRawConnection {
 
         Ok(())
     }
+
+    // Look up (or parse and cache) the server-side prepared statement for `query`.
+    async fn ensure_prepared(&mut self, query: &str, param_types: &[u32]) -> Result<Statement, Error> {
+        if let Some(statement) = self.statement_cache.get(query) {
+            return Ok(statement);
+        }
+
+        self.prepare(query, param_types).await
+    }
+
+    // Whether `query` already has a cached, server-side prepared statement,
+    // i.e. whether `ensure_prepared` could resolve it without a round trip.
+    pub(super) fn is_prepared(&mut self, query: &str) -> bool {
+        self.statement_cache.get(query).is_some()
+    }
+
+    /// Parse `query` once against the server and cache the resulting
+    /// prepared statement so future calls to `execute`/`fetch` (or another
+    /// call to `prepare`) against the same SQL text can skip re-parsing.
+    pub async fn prepare(&mut self, query: &str, param_types: &[u32]) -> Result<Statement, Error> {
+        if let Some(statement) = self.statement_cache.get(query) {
+            return Ok(statement);
+        }
+
+        let name = self.statement_cache.next_name();
+
+        self.write(protocol::Parse {
+            portal: &name,
+            query,
+            param_types,
+        });
+        self.write(protocol::Describe::Statement(&name));
+        self.write(protocol::Sync);
+        self.flush().await?;
+
+        let mut param_types = param_types.to_vec();
+        let mut columns = Vec::new();
+
+        loop {
+            match self.receive().await? {
+                Some(Message::ParseComplete) => {}
+                Some(Message::ParameterDescription(desc)) => param_types = desc.types,
+                Some(Message::RowDescription(desc)) => columns = desc.fields,
+                // No result columns (e.g. an INSERT/UPDATE/DELETE without
+                // RETURNING); `Sync`'s ReadyForQuery still follows.
+                Some(Message::NoData) => {}
+                Some(Message::ReadyForQuery(_)) => break,
+                _ => {}
+            }
+        }
+
+        let statement = Statement {
+            name,
+            param_types,
+            columns,
+        };
+
+        if let Some(evicted) = self.statement_cache.insert(query.to_string(), statement.clone()) {
+            self.close_statement(&evicted.name).await?;
+        }
+
+        Ok(statement)
+    }
+
+    /// Return a [`CancelToken`] that can be used to ask the server to
+    /// cancel whatever statement is currently running on this connection.
+    ///
+    /// The token is independent of this connection (it does not borrow
+    /// `self`), so it can be cloned out and used from another task while
+    /// a `fetch`/`execute` on this connection is still in flight.
+    pub fn cancel_token(&self) -> CancelToken {
+        CancelToken::new(self.target.clone(), self.process_id, self.secret_key)
+    }
+
+    /// Read the current value of a server run-time parameter (e.g.
+    /// `server_version`, `client_encoding`, `TimeZone`) as last reported by
+    /// a `ParameterStatus` message.
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.get(name).map(String::as_str)
+    }
+
+    /// A stream of asynchronous notifications delivered via `NOTIFY` on any
+    /// channel this connection is listening to (see `LISTEN`).
+    ///
+    /// Notifications that arrive while another method (e.g. `fetch`) is
+    /// reading from the connection are buffered internally and are yielded
+    /// here as soon as this stream is polled.
+    pub fn notifications(&mut self) -> BoxStream<'_, Result<Notification, Error>> {
+        Box::pin(stream::unfold(self, |conn| async move {
+            loop {
+                if let Some(notification) = conn.notifications.pop_front() {
+                    return Some((Ok(notification), conn));
+                }
+
+                match conn.receive().await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => return None,
+                    Err(err) => return Some((Err(err), conn)),
+                }
+            }
+        }))
+    }
+
+    // Close a server-side prepared statement that was evicted from the cache.
+    async fn close_statement(&mut self, name: &str) -> Result<(), Error> {
+        self.write(protocol::Close::Statement(name));
+        self.write(protocol::Sync);
+        self.flush().await?;
+
+        while !matches!(self.receive().await?, Some(Message::ReadyForQuery(_)) | None) {}
+
+        Ok(())
+    }
 }
 
 impl RawConnection for PostgresRawConnection {
@@ -169,9 +349,10 @@ impl RawConnection for PostgresRawConnection {
         query: &str,
         params: PostgresQueryParameters,
     ) -> BoxFuture<'c, Result<u64, Error>> {
-        finish(self, query, params, 0);
-
-        Box::pin(execute::execute(self))
+        Box::pin(async move {
+            finish(self, query, params, 0).await?;
+            execute::execute(self).await
+        })
     }
 
     fn fetch<'c>(
@@ -179,9 +360,14 @@ impl RawConnection for PostgresRawConnection {
         query: &str,
         params: PostgresQueryParameters,
     ) -> BoxStream<'c, Result<PostgresRow, Error>> {
-        finish(self, query, params, 0);
+        Box::pin(
+            async move {
+                finish(self, query, params, 0).await?;
 
-        Box::pin(fetch::fetch(self))
+                Ok(fetch::fetch(self))
+            }
+            .try_flatten_stream(),
+        )
     }
 
     fn fetch_optional<'c>(
@@ -189,22 +375,25 @@ impl RawConnection for PostgresRawConnection {
         query: &str,
         params: PostgresQueryParameters,
     ) -> BoxFuture<'c, Result<Option<PostgresRow>, Error>> {
-        finish(self, query, params, 1);
-
-        Box::pin(fetch_optional::fetch_optional(self))
+        Box::pin(async move {
+            finish(self, query, params, 1).await?;
+            fetch_optional::fetch_optional(self).await
+        })
     }
 }
 
-fn finish(conn: &mut PostgresRawConnection, query: &str, params: PostgresQueryParameters, limit: i32) {
-    conn.write(protocol::Parse {
-        portal: "",
-        query,
-        param_types: &*params.types,
-    });
+// Issue Bind/Execute/Sync for `query` against its prepared statement (re-used from the cache, or parsed now).
+async fn finish(
+    conn: &mut PostgresRawConnection,
+    query: &str,
+    params: PostgresQueryParameters,
+    limit: i32,
+) -> Result<(), Error> {
+    let statement = conn.ensure_prepared(query, &*params.types).await?;
 
     conn.write(protocol::Bind {
         portal: "",
-        statement: "",
+        statement: &statement.name,
         formats: &[1], // [BINARY]
         // TODO: Early error if there is more than i16
         values_len: params.types.len() as i16,
@@ -219,4 +408,6 @@ fn finish(conn: &mut PostgresRawConnection, query: &str, params: PostgresQueryPa
     });
 
     conn.write(protocol::Sync);
+
+    Ok(())
 }
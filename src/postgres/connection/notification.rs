@@ -0,0 +1,54 @@
+/// An asynchronous `NOTIFY` delivered to a connection that has issued a
+/// matching `LISTEN`, surfaced through [`PostgresRawConnection::notifications`].
+///
+/// [`PostgresRawConnection::notifications`]: super::PostgresRawConnection::notifications
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub process_id: u32,
+    pub channel: String,
+    pub payload: String,
+}
+
+impl Notification {
+    // A NotificationResponse body is int32 sending-process-id followed by
+    // two NUL-terminated UTF-8 strings: the channel name, then the payload.
+    //
+    // Returns `None` if `body` is too short to hold the process ID, which
+    // should never happen with a well-behaved server but must not panic on
+    // whatever bytes the server actually sent.
+    pub(super) fn parse(body: &[u8]) -> Option<Self> {
+        if body.len() < 4 {
+            return None;
+        }
+
+        let (process_id, body) = body.split_at(4);
+        let process_id = u32::from_be_bytes(process_id.try_into().unwrap());
+
+        let channel_end = body.iter().position(|&byte| byte == 0).unwrap_or(body.len());
+        let channel = String::from_utf8_lossy(&body[..channel_end]).into_owned();
+        let body = body.get(channel_end + 1..).unwrap_or(&[]);
+
+        let payload_end = body.iter().position(|&byte| byte == 0).unwrap_or(body.len());
+        let payload = String::from_utf8_lossy(&body[..payload_end]).into_owned();
+
+        Some(Self {
+            process_id,
+            channel,
+            payload,
+        })
+    }
+}
+
+// A ParameterStatus body is the same two-NUL-terminated-strings shape as a
+// notification's channel/payload, just naming a server parameter and its
+// current value (e.g. `server_version` / `13.4`) instead.
+pub(super) fn parse_parameter_status(body: &[u8]) -> (String, String) {
+    let name_end = body.iter().position(|&byte| byte == 0).unwrap_or(body.len());
+    let name = String::from_utf8_lossy(&body[..name_end]).into_owned();
+    let rest = body.get(name_end + 1..).unwrap_or(&[]);
+
+    let value_end = rest.iter().position(|&byte| byte == 0).unwrap_or(rest.len());
+    let value = String::from_utf8_lossy(&rest[..value_end]).into_owned();
+
+    (name, value)
+}
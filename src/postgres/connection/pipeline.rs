@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::task::{Context, Poll, Waker};
+
+use futures_util::TryStreamExt as _;
+
+use super::{execute, fetch, PostgresRawConnection};
+use crate::error::Error;
+use crate::postgres::{PostgresQueryParameters, PostgresRow};
+
+// Default number of requests that may be written but not yet drained
+// before `is_ready` starts reporting back-pressure.
+const DEFAULT_WINDOW: usize = 50;
+
+enum QueuedRequest {
+    Execute,
+    Fetch,
+}
+
+/// The result of draining one request queued on a [`Pipeline`].
+pub enum PipelineResponse {
+    Execute(u64),
+    Fetch(Vec<PostgresRow>),
+}
+
+/// Lets multiple already-prepared queries be written back-to-back -- each
+/// with its own `Bind`/`Execute`/`Sync` -- before any of their replies are
+/// read, trading the latency of one round trip per query for one shared
+/// round trip, while still dispatching replies to the right caller in
+/// the order the queries were sent (each request's replies are bounded
+/// by its own `Sync`/`ReadyForQuery`).
+///
+/// Because nothing stops a producer from queuing requests faster than
+/// the backend (or the caller) can drain their replies, `is_ready` (and
+/// its `Future`-friendly counterpart `poll_ready`) lets callers
+/// cooperatively throttle queuing to a configurable window.
+pub struct Pipeline<'c> {
+    conn: &'c mut PostgresRawConnection,
+    window: usize,
+    queued: VecDeque<QueuedRequest>,
+
+    // Woken by `next()` as soon as draining a response frees up a slot;
+    // set by `poll_ready` while it's reporting back-pressure.
+    waker: Option<Waker>,
+}
+
+impl<'c> Pipeline<'c> {
+    pub(super) fn new(conn: &'c mut PostgresRawConnection) -> Self {
+        Self {
+            conn,
+            window: DEFAULT_WINDOW,
+            queued: VecDeque::new(),
+            waker: None,
+        }
+    }
+
+    /// Override the default window of requests that may be in flight
+    /// (written but not yet drained with `next`) at once.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Whether another request can be queued without exceeding the window.
+    pub fn is_ready(&self) -> bool {
+        self.queued.len() < self.window
+    }
+
+    /// Resolve once the pipeline has room for another request. Parks until
+    /// `next()` drains a response and frees up a slot instead of spinning.
+    pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_ready() {
+            Poll::Ready(())
+        } else {
+            self.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Queue an `execute`-style request: write its `Bind`/`Execute`/`Sync`
+    /// now, but don't wait for (or read) any of its replies.
+    ///
+    /// `query` must already be prepared on this connection with
+    /// [`PostgresRawConnection::prepare`] -- preparing it here on a
+    /// cache miss would require a round trip that reads out-of-turn
+    /// through replies already queued ahead of it.
+    ///
+    /// # Panics
+    /// Panics if `is_ready()` is false; check `is_ready`/`poll_ready` first.
+    pub async fn execute(&mut self, query: &str, params: PostgresQueryParameters) -> Result<(), Error> {
+        self.enqueue(query, params, 0, QueuedRequest::Execute).await
+    }
+
+    /// Queue a `fetch`-style request; see [`Pipeline::execute`].
+    pub async fn fetch(&mut self, query: &str, params: PostgresQueryParameters) -> Result<(), Error> {
+        self.enqueue(query, params, 0, QueuedRequest::Fetch).await
+    }
+
+    // `finish` (shared with the non-pipelined `execute`/`fetch`) falls back
+    // to `ensure_prepared`'s synchronous Parse/Describe/Sync round trip on a
+    // statement-cache miss. That round trip reads with `receive()` all the
+    // way to its own `ReadyForQuery`, which would consume the in-flight
+    // replies of requests already queued ahead of it here, corrupting FIFO
+    // dispatch. So pipelining requires the statement to already be cached;
+    // callers must `prepare()` it first.
+    async fn enqueue(
+        &mut self,
+        query: &str,
+        params: PostgresQueryParameters,
+        limit: i32,
+        request: QueuedRequest,
+    ) -> Result<(), Error> {
+        assert!(self.is_ready(), "pipeline window exceeded; await poll_ready before queuing more");
+
+        if !self.conn.is_prepared(query) {
+            return Err(Error::Configuration(
+                format!("`{query}` must be prepared with `PostgresRawConnection::prepare` before it can be pipelined").into(),
+            ));
+        }
+
+        super::finish(self.conn, query, params, limit).await?;
+        self.conn.flush().await?;
+        self.queued.push_back(request);
+
+        Ok(())
+    }
+
+    /// Read back the response to the oldest still-unread queued request,
+    /// waiting for its `ReadyForQuery` boundary. Returns `None` once every
+    /// queued request has been drained.
+    pub async fn next(&mut self) -> Option<Result<PipelineResponse, Error>> {
+        let request = self.queued.pop_front()?;
+
+        if self.is_ready() {
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+
+        let result = match request {
+            QueuedRequest::Execute => execute::execute(self.conn).await.map(PipelineResponse::Execute),
+            QueuedRequest::Fetch => fetch::fetch(self.conn).try_collect().await.map(PipelineResponse::Fetch),
+        };
+
+        Some(result)
+    }
+}
+
+impl PostgresRawConnection {
+    /// Start pipelining requests on this connection: see [`Pipeline`].
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline::new(self)
+    }
+}
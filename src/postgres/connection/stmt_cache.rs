@@ -0,0 +1,79 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::protocol;
+
+/// A server-side prepared statement plus the parameter and result
+/// metadata that was captured when it was parsed.
+#[derive(Debug, Clone)]
+pub(super) struct Statement {
+    pub(super) name: String,
+    pub(super) param_types: Vec<u32>,
+    pub(super) columns: Vec<protocol::FieldDescription>,
+}
+
+/// A fixed-capacity, LRU-evicting cache of prepared [`Statement`]s keyed
+/// by the exact SQL text that produced them.
+///
+/// Evicted entries are returned to the caller so the server-side
+/// statement can be closed with `protocol::Close`; this type only
+/// tracks what is known locally and never talks to the connection.
+pub(super) struct StatementCache {
+    capacity: usize,
+    next_id: u32,
+    entries: HashMap<String, Statement>,
+    // Back of the queue is most-recently-used.
+    recency: VecDeque<String>,
+}
+
+impl StatementCache {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            next_id: 0,
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+        }
+    }
+
+    pub(super) fn get(&mut self, query: &str) -> Option<Statement> {
+        if self.entries.contains_key(query) {
+            self.touch(query);
+        }
+
+        self.entries.get(query).cloned()
+    }
+
+    /// Allocate the next server-side statement name, e.g. `s1`, `s2`, ...
+    pub(super) fn next_name(&mut self) -> String {
+        self.next_id += 1;
+        format!("s{}", self.next_id)
+    }
+
+    /// Insert a newly-parsed statement, evicting the least-recently-used
+    /// entry first if the cache is already at capacity. Returns the
+    /// evicted statement, if any, so its server-side name can be closed.
+    pub(super) fn insert(&mut self, query: String, statement: Statement) -> Option<Statement> {
+        let evicted = if self.entries.len() >= self.capacity {
+            self.evict_one()
+        } else {
+            None
+        };
+
+        self.recency.push_back(query.clone());
+        self.entries.insert(query, statement);
+
+        evicted
+    }
+
+    fn touch(&mut self, query: &str) {
+        if let Some(pos) = self.recency.iter().position(|cached| cached == query) {
+            let query = self.recency.remove(pos).unwrap();
+            self.recency.push_back(query);
+        }
+    }
+
+    fn evict_one(&mut self) -> Option<Statement> {
+        let query = self.recency.pop_front()?;
+        self.entries.remove(&query)
+    }
+}